@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use cosmic::app::{Core, Task};
 use cosmic::iced::futures::{SinkExt, StreamExt};
@@ -15,6 +17,8 @@ use tokio::sync::mpsc;
 use tracing::info;
 
 use crate::config::ConnectConfig;
+use crate::events::ConnectEvent;
+use crate::identity::DeviceIdentity;
 use crate::{fl, APP_ID};
 
 pub struct CosmicConnect {
@@ -25,6 +29,26 @@ pub struct CosmicConnect {
     kdeconnect: Option<KdeConnect>,
     kdeconnect_client_action_sender: Option<mpsc::UnboundedSender<ClientAction>>,
     connections: HashMap<String, DeviceState>,
+    /// Contents of the "Add device by IP" text field.
+    manual_peer_input: String,
+    /// In-flight file transfers, keyed by transfer id.
+    transfers: HashMap<String, Transfer>,
+    /// Paired devices that are known but currently unreachable.
+    unreachable_devices: HashSet<String>,
+    /// This applet's persistent TLS identity, shared with the backend so it
+    /// presents the same certificate on every connection.
+    identity: DeviceIdentity,
+    /// Files dropped onto the popup, waiting to be sent to a chosen device.
+    dropped_files: Vec<PathBuf>,
+}
+
+/// Progress of a single outgoing or incoming file transfer.
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    device_id: DeviceId,
+    name: String,
+    transferred: u64,
+    total: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -32,19 +56,23 @@ pub enum Message {
     TogglePopup,
     PopupClosed(Id),
     UpdateConfig(ConnectConfig),
-    KdeConnect(KdeConnectEvent),
-    DeviceUpdate(DeviceResponse),
+    Event(ConnectEvent),
     DisconnectDevice(Box<DeviceState>),
     Broadcast,
     UpdateState(Box<DeviceState>),
     PairDevice(DeviceId),
     UnPairDevice(DeviceId),
+    ConfirmPairing(DeviceId),
+    RejectPairing(DeviceId),
     SendPing((DeviceId, String)),
-}
-
-#[derive(Debug, Clone)]
-pub enum KdeConnectEvent {
-    Connected((KdeConnect, mpsc::UnboundedSender<ClientAction>)),
+    ToggleDiscovery(bool),
+    ManualPeerInputChanged(String),
+    AddManualPeer,
+    SendFiles(DeviceId),
+    FilesPicked(DeviceId, Vec<PathBuf>),
+    CancelTransfer(String),
+    FileDropped(PathBuf),
+    SendDroppedFiles(DeviceId),
 }
 
 impl Application for CosmicConnect {
@@ -65,7 +93,18 @@ impl Application for CosmicConnect {
     }
 
     fn init(core: Core, _flags: Self::Flags) -> (Self, Task<Self::Message>) {
-        let config = ConnectConfig::config();
+        let mut config = ConnectConfig::config();
+
+        let device_name = hostname::get()
+            .ok()
+            .and_then(|name| name.into_string().ok())
+            .unwrap_or_else(|| "COSMIC Connect".to_string());
+
+        let identity = ConnectConfig::config_handler()
+            .map(|handler| config.ensure_identity(&handler, &device_name))
+            .unwrap_or_else(|| DeviceIdentity::generate(&device_name));
+
+        info!("Device identity fingerprint: {}", identity.fingerprint());
 
         let app = CosmicConnect {
             core,
@@ -73,6 +112,11 @@ impl Application for CosmicConnect {
             kdeconnect: None,
             kdeconnect_client_action_sender: None,
             connections: HashMap::new(),
+            manual_peer_input: String::new(),
+            transfers: HashMap::new(),
+            unreachable_devices: HashSet::new(),
+            identity,
+            dropped_files: Vec::new(),
 
             config,
         };
@@ -90,7 +134,7 @@ impl Application for CosmicConnect {
         let kdeconnect = Subscription::run_with_id(
             1,
             stream::channel(100, |mut output| async move {
-                let (kdeconnect, client_action_sender, mut device_update) = KdeConnect::new();
+                let (kdeconnect, action_sender, mut device_update) = KdeConnect::new();
                 let mut kconnect = kdeconnect.clone();
 
                 tokio::task::spawn(async move {
@@ -98,24 +142,31 @@ impl Application for CosmicConnect {
                 });
 
                 let _ = output
-                    .send(Message::KdeConnect(KdeConnectEvent::Connected((
-                        kdeconnect,
-                        client_action_sender,
-                    ))))
+                    .send(Message::Event(ConnectEvent::Connected {
+                        client: kdeconnect,
+                        action_sender,
+                    }))
                     .await;
 
-                let mut out = output.clone();
-
-                tokio::task::spawn(async move {
-                    while let Some(update) = device_update.next().await {
-                        let _ = out.send(Message::DeviceUpdate(update)).await;
-                    }
-                });
+                while let Some(update) = device_update.next().await {
+                    let _ = output
+                        .send(Message::Event(ConnectEvent::Device(update)))
+                        .await;
+                }
             }),
         );
 
         subscriptions.push(kdeconnect);
 
+        let file_drop = iced::event::listen_with(|event, _status, _id| match event {
+            iced::Event::Window(iced::window::Event::FileDropped(path)) => {
+                Some(Message::FileDropped(path))
+            }
+            _ => None,
+        });
+
+        subscriptions.push(file_drop);
+
         let config = self
             .core()
             .watch_config::<ConnectConfig>(Self::APP_ID)
@@ -142,31 +193,96 @@ impl Application for CosmicConnect {
                 .into(),
         ]));
 
+        content_list = content_list.add(settings::item(
+            "Discover devices automatically",
+            widget::toggler(self.config.discovery_enabled).on_toggle(Message::ToggleDiscovery),
+        ));
+
+        content_list = content_list.add(settings::flex_item_row(vec![
+            widget::text_input("Add device by IP", &self.manual_peer_input)
+                .on_input(Message::ManualPeerInputChanged)
+                .on_submit(Message::AddManualPeer)
+                .into(),
+            widget::button::standard("Add")
+                .on_press(Message::AddManualPeer)
+                .into(),
+        ]));
+
+        if !self.dropped_files.is_empty() {
+            content_list = content_list.add(settings::item(
+                "Dropped files",
+                widget::text(format!(
+                    "{} file(s) ready to send — pick a device below",
+                    self.dropped_files.len()
+                )),
+            ));
+        }
+
         for state in self.connections.values() {
+            let reachable = !self.unreachable_devices.contains(&state.device_id.id);
+
             content_list = content_list.add(settings::item_row(vec![
-                widget::text::monotext(state.device_id.name.clone()).into(),
+                widget::text::monotext(if reachable {
+                    state.device_id.name.clone()
+                } else {
+                    format!("{} (offline)", state.device_id.name)
+                })
+                .into(),
                 widget::button::standard("Disconnect")
-                    .on_press(Message::DisconnectDevice(Box::new(state.to_owned())))
+                    .on_press_maybe(
+                        reachable.then(|| Message::DisconnectDevice(Box::new(state.to_owned()))),
+                    )
                     .into(),
                 if self.is_paired(state.device_id.clone()) {
                     widget::button::standard("UnPair")
-                        .on_press(Message::UnPairDevice(state.device_id.clone()))
+                        .on_press_maybe(
+                            reachable.then(|| Message::UnPairDevice(state.device_id.clone())),
+                        )
                         .into()
                 } else {
                     widget::button::standard("Pair")
-                        .on_press(Message::PairDevice(state.device_id.clone()))
+                        .on_press_maybe(
+                            reachable.then(|| Message::PairDevice(state.device_id.clone())),
+                        )
                         .into()
                 },
                 widget::button::standard("Send Ping")
-                    .on_press(Message::SendPing((
-                        state.device_id.clone(),
-                        "Hello From COSMIC APPLET!".to_string(),
-                    )))
+                    .on_press_maybe(reachable.then(|| {
+                        Message::SendPing((
+                            state.device_id.clone(),
+                            "Hello From COSMIC APPLET!".to_string(),
+                        ))
+                    }))
+                    .into(),
+                widget::button::standard("Send File")
+                    .on_press_maybe(reachable.then(|| Message::SendFiles(state.device_id.clone())))
                     .into(),
             ]));
 
+            if reachable && !self.dropped_files.is_empty() {
+                content_list =
+                    content_list.add(settings::item_row(vec![widget::button::standard(format!(
+                        "Send {} dropped file(s) here",
+                        self.dropped_files.len()
+                    ))
+                    .on_press(Message::SendDroppedFiles(state.device_id.clone()))
+                    .into()]));
+            }
+
             let mut section = settings::section().title(state.device_id.to_string());
 
+            if let PairingState::Requested { code } = &state.pairing_state {
+                section = section.add(settings::item_row(vec![
+                    widget::text(format!("Verify code: {code}")).into(),
+                    widget::button::standard("Accept")
+                        .on_press(Message::ConfirmPairing(state.device_id.clone()))
+                        .into(),
+                    widget::button::standard("Reject")
+                        .on_press(Message::RejectPairing(state.device_id.clone()))
+                        .into(),
+                ]));
+            }
+
             if let Some(networks) = state.connectivity.as_ref() {
                 for (_, network) in &networks.signal_strengths {
                     section = section.add(settings::item(
@@ -193,6 +309,23 @@ impl Application for CosmicConnect {
                 None
             });
 
+            for (transfer_id, transfer) in self
+                .transfers
+                .iter()
+                .filter(|(_, transfer)| transfer.device_id.id == state.device_id.id)
+            {
+                section = section.add(settings::item_row(vec![
+                    widget::text(format!(
+                        "{} ({}/{} bytes)",
+                        transfer.name, transfer.transferred, transfer.total
+                    ))
+                    .into(),
+                    widget::button::standard("Cancel")
+                        .on_press(Message::CancelTransfer(transfer_id.clone()))
+                        .into(),
+                ]));
+            }
+
             content_list = content_list.add(section);
         }
 
@@ -234,28 +367,100 @@ impl Application for CosmicConnect {
             Message::UpdateConfig(config) => {
                 self.config = config;
             }
-            Message::KdeConnect(event) => {
-                match event {
-                    KdeConnectEvent::Connected((client, client_action_sender)) => {
-                        info!("Connected to backend server");
-                        self.kdeconnect = Some(client);
-                        self.kdeconnect_client_action_sender = Some(client_action_sender);
+            Message::Event(event) => match event {
+                ConnectEvent::Connected {
+                    client,
+                    action_sender,
+                } => {
+                    info!("Connected to backend server");
+
+                    action_sender
+                        .send(ClientAction::SetIdentity(self.identity.clone()))
+                        .unwrap_or_else(|err| {
+                            tracing::warn!("failed to send identity action: {}", err);
+                        });
+
+                    action_sender
+                        .send(ClientAction::SetDiscovery(self.config.discovery_enabled))
+                        .unwrap_or_else(|err| {
+                            tracing::warn!("failed to send discovery action: {}", err);
+                        });
+
+                    for addr in &self.config.manual_peers {
+                        action_sender
+                            .send(ClientAction::AddManualPeer(*addr))
+                            .unwrap_or_else(|err| {
+                                tracing::warn!("failed to send manual peer action: {}", err);
+                            });
                     }
-                };
-            }
-            Message::DeviceUpdate(response) => match response {
-                DeviceResponse::Refresh(state) => {
-                    info!("Refreshing connection.");
-                    return Task::done(Action::App(Message::UpdateState(state)));
-                }
-                DeviceResponse::SyncClipboard(content) => {
-                    return cosmic::iced::clipboard::write(content);
+
+                    self.kdeconnect = Some(client);
+                    self.kdeconnect_client_action_sender = Some(action_sender);
                 }
+                ConnectEvent::Device(response) => match response {
+                    DeviceResponse::Refresh(state) => {
+                        info!("Refreshing connection.");
+                        return Task::done(Action::App(Message::UpdateState(state)));
+                    }
+                    DeviceResponse::SyncClipboard(content) => {
+                        return cosmic::iced::clipboard::write(content);
+                    }
+                    DeviceResponse::PairingRequest { device_id, code } => {
+                        info!(
+                            "Pairing requested by {}, verification code {}",
+                            device_id, code
+                        );
+                    }
+                    DeviceResponse::IncomingFile {
+                        device_id,
+                        transfer_id,
+                        name,
+                        size,
+                    } => {
+                        info!("Incoming file {} ({} bytes) from {}", name, size, device_id);
+                        self.transfers.insert(
+                            transfer_id,
+                            Transfer {
+                                device_id,
+                                name,
+                                transferred: 0,
+                                total: size,
+                            },
+                        );
+                    }
+                    DeviceResponse::TransferProgress {
+                        transfer_id,
+                        transferred,
+                        total,
+                        ..
+                    } => {
+                        let done = if let Some(transfer) = self.transfers.get_mut(&transfer_id) {
+                            transfer.transferred = transferred;
+                            transfer.total = total;
+                            transferred >= total
+                        } else {
+                            false
+                        };
+
+                        if done {
+                            self.transfers.remove(&transfer_id);
+                        }
+                    }
+                    DeviceResponse::Expired(device_id) => {
+                        info!("Device expired: {}", device_id);
+
+                        if self.is_paired(device_id.clone()) {
+                            self.unreachable_devices.insert(device_id.id.clone());
+                        } else {
+                            self.connections.remove(&device_id.id);
+                            self.unreachable_devices.remove(&device_id.id);
+                        }
+                    }
+                },
             },
             Message::DisconnectDevice(device) => {
                 device.send(DeviceAction::Disconnect);
                 self.connections.remove(&device.device_id.id);
-                self.kdeconnect = None;
             }
             Message::Broadcast => {
                 if let Some(sender) = &self.kdeconnect_client_action_sender {
@@ -265,7 +470,20 @@ impl Application for CosmicConnect {
                 }
             }
             Message::UpdateState(state) => {
+                let remote_fingerprint = state.remote_identity.fingerprint();
+
+                if let Some(trusted) = self.config.trusted_fingerprints.get(&state.device_id.id) {
+                    if trusted != &remote_fingerprint {
+                        tracing::warn!(
+                            "rejecting {}: certificate fingerprint changed since pairing",
+                            state.device_id
+                        );
+                        return Task::none();
+                    }
+                }
+
                 info!("Updating device state: {:?}", state);
+                self.unreachable_devices.remove(&state.device_id.id);
                 self.connections.insert(state.device_id.id.clone(), *state);
             }
             Message::PairDevice(device) => {
@@ -274,12 +492,42 @@ impl Application for CosmicConnect {
                 self.connections.get(&device.id).iter().for_each(|state| {
                     state.send(DeviceAction::Pair);
                 });
+            }
+            Message::ConfirmPairing(device) => {
+                info!("Confirming pairing for device: {}", device.id);
+
+                self.connections.get(&device.id).iter().for_each(|state| {
+                    state.send(DeviceAction::ConfirmPairing);
+                });
 
                 let handler = ConnectConfig::config_handler().unwrap();
 
                 if let Err(err) = self.config.set_paired(&handler, Some(device.clone())) {
                     tracing::warn!("failed to save config: {}", err);
                 }
+
+                if let Some(fingerprint) = self
+                    .connections
+                    .get(&device.id)
+                    .map(|state| state.remote_identity.fingerprint())
+                {
+                    let mut trusted_fingerprints = self.config.trusted_fingerprints.clone();
+                    trusted_fingerprints.insert(device.id.clone(), fingerprint);
+
+                    if let Err(err) = self
+                        .config
+                        .set_trusted_fingerprints(&handler, trusted_fingerprints)
+                    {
+                        tracing::warn!("failed to save config: {}", err);
+                    }
+                }
+            }
+            Message::RejectPairing(device) => {
+                info!("Rejecting pairing for device: {}", device.id);
+
+                self.connections.get(&device.id).iter().for_each(|state| {
+                    state.send(DeviceAction::RejectPairing);
+                });
             }
             Message::UnPairDevice(device) => {
                 self.connections.get(&device.id).iter().for_each(|state| {
@@ -294,13 +542,111 @@ impl Application for CosmicConnect {
                     tracing::warn!("failed to save config: {}", err);
                 }
 
+                let mut trusted_fingerprints = self.config.trusted_fingerprints.clone();
+                trusted_fingerprints.remove(&device.id);
+
+                if let Err(err) = self
+                    .config
+                    .set_trusted_fingerprints(&handler, trusted_fingerprints)
+                {
+                    tracing::warn!("failed to save config: {}", err);
+                }
+
                 self.connections.remove(&device.id);
+                self.unreachable_devices.remove(&device.id);
             }
             Message::SendPing((id, msg)) => {
                 self.connections.get(&id.id).iter().for_each(|state| {
                     state.send(DeviceAction::Ping(msg.clone()));
                 });
             }
+            Message::ToggleDiscovery(enabled) => {
+                self.config.discovery_enabled = enabled;
+
+                let handler = ConnectConfig::config_handler().unwrap();
+
+                if let Err(err) = self.config.set_discovery_enabled(&handler, enabled) {
+                    tracing::warn!("failed to save config: {}", err);
+                }
+
+                if let Some(sender) = &self.kdeconnect_client_action_sender {
+                    sender
+                        .send(ClientAction::SetDiscovery(enabled))
+                        .unwrap_or_else(|err| {
+                            tracing::warn!("failed to send discovery action: {}", err);
+                        });
+                }
+            }
+            Message::ManualPeerInputChanged(input) => {
+                self.manual_peer_input = input;
+            }
+            Message::AddManualPeer => {
+                let input = self.manual_peer_input.trim();
+
+                match input.parse::<SocketAddr>() {
+                    Ok(addr) => {
+                        let handler = ConnectConfig::config_handler().unwrap();
+                        let mut manual_peers = self.config.manual_peers.clone();
+                        manual_peers.push(addr);
+
+                        if let Err(err) = self.config.set_manual_peers(&handler, manual_peers) {
+                            tracing::warn!("failed to save config: {}", err);
+                        }
+
+                        if let Some(sender) = &self.kdeconnect_client_action_sender {
+                            sender
+                                .send(ClientAction::AddManualPeer(addr))
+                                .unwrap_or_else(|err| {
+                                    tracing::warn!("failed to send manual peer action: {}", err);
+                                });
+                        }
+
+                        self.manual_peer_input.clear();
+                    }
+                    Err(err) => {
+                        tracing::warn!("invalid manual peer address {:?}: {}", input, err);
+                    }
+                }
+            }
+            Message::SendFiles(device) => {
+                return Task::perform(
+                    async move {
+                        rfd::AsyncFileDialog::new()
+                            .pick_files()
+                            .await
+                            .map(|files| {
+                                files
+                                    .into_iter()
+                                    .map(|file| file.path().to_path_buf())
+                                    .collect()
+                            })
+                            .unwrap_or_default()
+                    },
+                    move |files| Action::App(Message::FilesPicked(device.clone(), files)),
+                );
+            }
+            Message::FilesPicked(device, files) => {
+                self.send_files(device, files);
+            }
+            Message::FileDropped(path) => {
+                self.dropped_files.push(path);
+            }
+            Message::SendDroppedFiles(device) => {
+                let files = std::mem::take(&mut self.dropped_files);
+                self.send_files(device, files);
+            }
+            Message::CancelTransfer(transfer_id) => {
+                if let Some(transfer) = self.transfers.get(&transfer_id) {
+                    self.connections
+                        .get(&transfer.device_id.id)
+                        .iter()
+                        .for_each(|state| {
+                            state.send(DeviceAction::CancelTransfer(transfer_id.clone()));
+                        });
+                }
+
+                self.transfers.remove(&transfer_id);
+            }
         }
         Task::none()
     }
@@ -316,4 +662,39 @@ impl CosmicConnect {
             .get(&device_id.id)
             .is_some_and(|state| state.pairing_state == PairingState::Paired)
     }
+
+    /// Sends `files` to `device`, tracking the transfer so its progress and
+    /// cancellation can be driven by `self.transfers` like an incoming one.
+    fn send_files(&mut self, device: DeviceId, files: Vec<PathBuf>) {
+        if files.is_empty() {
+            return;
+        }
+
+        let Some(state) = self.connections.get(&device.id) else {
+            return;
+        };
+
+        let transfer_id = uuid::Uuid::new_v4().to_string();
+        let name = files
+            .iter()
+            .map(|file| {
+                file.file_name()
+                    .map_or_else(|| file.to_string_lossy(), |name| name.to_string_lossy())
+                    .into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        self.transfers.insert(
+            transfer_id.clone(),
+            Transfer {
+                device_id: device,
+                name,
+                transferred: 0,
+                total: 0,
+            },
+        );
+
+        state.send(DeviceAction::SendFiles(transfer_id, files));
+    }
 }
@@ -1,15 +1,40 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 
 use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
 use kdeconnect::device::{ConnectedId, Linked};
 
+use crate::identity::DeviceIdentity;
 use crate::{APP_ID, CONFIG_VERSION};
 
-#[derive(Debug, Default, Clone, CosmicConfigEntry, Eq, PartialEq)]
+#[derive(Debug, Clone, CosmicConfigEntry, Eq, PartialEq)]
 #[version = 1]
 pub struct ConnectConfig {
     pub last_connections: HashSet<Linked>,
     pub paired: Vec<ConnectedId>,
+    /// Whether devices are discovered automatically via mDNS announce/listen.
+    pub discovery_enabled: bool,
+    /// Devices paired by address instead of (or in addition to) discovery.
+    pub manual_peers: Vec<SocketAddr>,
+    /// This applet's own long-lived TLS identity, generated once and reused
+    /// across restarts so existing pairings stay valid.
+    pub identity: Option<DeviceIdentity>,
+    /// Certificate fingerprint recorded for each paired device, keyed by
+    /// device id, so a spoofed device can't inherit a trusted pairing.
+    pub trusted_fingerprints: HashMap<String, String>,
+}
+
+impl Default for ConnectConfig {
+    fn default() -> Self {
+        Self {
+            last_connections: HashSet::new(),
+            paired: Vec::new(),
+            discovery_enabled: true,
+            manual_peers: Vec::new(),
+            identity: None,
+            trusted_fingerprints: HashMap::new(),
+        }
+    }
 }
 
 impl ConnectConfig {
@@ -27,4 +52,24 @@ impl ConnectConfig {
             None => ConnectConfig::default(),
         }
     }
+
+    /// Returns this applet's persisted identity, generating and saving one
+    /// under `device_name` the first time it's needed.
+    pub fn ensure_identity(
+        &mut self,
+        handler: &cosmic_config::Config,
+        device_name: &str,
+    ) -> DeviceIdentity {
+        if let Some(identity) = &self.identity {
+            return identity.clone();
+        }
+
+        let identity = DeviceIdentity::generate(device_name);
+
+        if let Err(err) = self.set_identity(handler, Some(identity.clone())) {
+            tracing::warn!("failed to save device identity: {}", err);
+        }
+
+        identity
+    }
 }
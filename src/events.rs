@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use kdeconnect::device::DeviceResponse;
+use kdeconnect::{ClientAction, KdeConnect};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Every signal the backend can send to the applet.
+///
+/// This is the single typed seam between `kdeconnect` and the UI: adding a
+/// plugin (files, notifications, media, ...) means adding a variant here
+/// rather than wiring up another subscription channel.
+#[derive(Debug, Clone)]
+pub enum ConnectEvent {
+    /// The backend client has started and is ready to receive actions.
+    Connected {
+        client: KdeConnect,
+        action_sender: UnboundedSender<ClientAction>,
+    },
+    /// A per-device update (state refresh, clipboard sync, pairing, transfer
+    /// progress, expiry, ...).
+    Device(DeviceResponse),
+}
@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use rcgen::{CertificateParams, DnType, KeyPair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A stable TLS identity for this device.
+///
+/// Generated once and persisted alongside `paired` so restarting the applet
+/// does not invalidate every existing pairing by presenting a fresh
+/// certificate to already-trusted peers.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DeviceIdentity {
+    pub device_uuid: String,
+    pub certificate_pem: String,
+    pub private_key_pem: String,
+}
+
+impl DeviceIdentity {
+    /// Generates a new self-signed identity for `device_name`.
+    pub fn generate(device_name: &str) -> Self {
+        let device_uuid = uuid::Uuid::new_v4().to_string();
+
+        let mut params =
+            CertificateParams::new(vec![device_uuid.clone()]).expect("valid certificate params");
+        params
+            .distinguished_name
+            .push(DnType::CommonName, device_name);
+
+        let key_pair = KeyPair::generate().expect("key pair generation");
+        let certificate = params
+            .self_signed(&key_pair)
+            .expect("self-signed certificate");
+
+        Self {
+            device_uuid,
+            certificate_pem: certificate.pem(),
+            private_key_pem: key_pair.serialize_pem(),
+        }
+    }
+
+    /// Hex-encoded SHA-256 digest of this identity's certificate, taken over
+    /// its PEM text.
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.certificate_pem.as_bytes());
+        hex::encode(digest)
+    }
+}
@@ -5,6 +5,8 @@ use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 mod app;
 mod core;
+mod events;
+mod identity;
 
 fn main() -> cosmic::iced::Result {
     let subscriber = FmtSubscriber::builder()